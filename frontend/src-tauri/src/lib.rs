@@ -56,9 +56,17 @@ pub fn run() {
                             }
                         }
                         "quit" => {
-                            // Stop backend before quitting
+                            // Save every window's geometry and stop the
+                            // backend before quitting.
+                            window_state::save_all_window_states(
+                                app,
+                                window_state::StateFlags::default(),
+                            );
+                            // Block until the graceful stop (or its escalation
+                            // to a hard kill) finishes, so `app.exit` doesn't
+                            // tear the process down mid-shutdown.
                             let handle = app.clone();
-                            tauri::async_runtime::spawn(async move {
+                            tauri::async_runtime::block_on(async move {
                                 let _ = sidecar::stop_backend_internal(&handle).await;
                             });
                             app.exit(0);
@@ -95,10 +103,14 @@ pub fn run() {
                             // Wait for backend to be healthy, then show window
                             for _ in 0..30 {
                                 if sidecar::check_health().await {
+                                    sidecar::emit_status(&handle, sidecar::BackendStatus::Healthy);
                                     SETUP_COMPLETE.store(true, Ordering::SeqCst);
                                     if let Some(window) = handle.get_webview_window("main") {
                                         // Restore window state if available
-                                        window_state::restore_window_state(&window);
+                                        window_state::restore_window_state(
+                                            &window,
+                                            window_state::StateFlags::default(),
+                                        );
                                         let _ = window.show();
                                         let _ = window.set_focus();
                                     }
@@ -107,6 +119,7 @@ pub fn run() {
                                 tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
                             }
                             log::error!("Backend health check timed out");
+                            sidecar::emit_status(&handle, sidecar::BackendStatus::Unhealthy);
                         }
                         Err(e) => {
                             log::error!("Failed to start backend: {}", e);
@@ -114,10 +127,15 @@ pub fn run() {
                     }
                 });
             } else {
-                // Show window immediately for setup
+                // Show window immediately for setup. Restore size but skip
+                // position: the setup window should land centered on
+                // whatever monitor layout the user has right now rather
+                // than a stale saved coordinate from a previous machine.
                 if let Some(window) = app_handle.get_webview_window("main") {
-                    // Restore window state if available
-                    window_state::restore_window_state(&window);
+                    window_state::restore_window_state(
+                        &window,
+                        window_state::StateFlags::default() - window_state::StateFlags::POSITION,
+                    );
                     let _ = window.show();
                     let _ = window.set_focus();
                 }
@@ -129,14 +147,14 @@ pub fn run() {
             match event {
                 WindowEvent::CloseRequested { api, .. } => {
                     // Save window state before hiding
-                    window_state::save_window_state(window);
+                    window_state::save_window_state(window, window_state::StateFlags::default());
                     // Hide window instead of closing (minimize to tray)
                     let _ = window.hide();
                     api.prevent_close();
                 }
                 WindowEvent::Resized(_) | WindowEvent::Moved(_) => {
                     // Save window state on resize/move
-                    window_state::save_window_state(window);
+                    window_state::save_window_state(window, window_state::StateFlags::default());
                 }
                 _ => {}
             }
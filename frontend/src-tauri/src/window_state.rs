@@ -1,21 +1,52 @@
 //! Window state persistence module
 //!
-//! Saves and restores window position and size across app restarts.
+//! Saves and restores window position and size across app restarts, per
+//! window label, and lets callers choose which attributes are persisted.
 
+use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use tauri::{WebviewWindow, Window};
+use tauri::{AppHandle, Manager, WebviewWindow, Window};
 
-#[derive(Debug, Serialize, Deserialize)]
+bitflags! {
+    /// Which attributes of a window's state should be saved and restored.
+    ///
+    /// Mirrors the flag-driven model used by the community window-state
+    /// plugin so callers can, e.g., restore size but not position, or skip
+    /// a transient popup entirely.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct StateFlags: u32 {
+        const POSITION = 1 << 0;
+        const SIZE = 1 << 1;
+        const MAXIMIZED = 1 << 2;
+        const FULLSCREEN = 1 << 3;
+        const VISIBLE = 1 << 4;
+        const DECORATIONS = 1 << 5;
+    }
+}
+
+impl Default for StateFlags {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct WindowState {
     x: i32,
     y: i32,
     width: u32,
     height: u32,
     maximized: bool,
+    fullscreen: bool,
+    visible: bool,
+    decorations: bool,
 }
 
+type WindowStateMap = HashMap<String, WindowState>;
+
 /// Get the path to the window state file
 fn get_state_file_path() -> PathBuf {
     // Store in exe directory for production, current dir for dev
@@ -29,90 +60,128 @@ fn get_state_file_path() -> PathBuf {
     }
 }
 
-/// Save the current window state to file
-pub fn save_window_state(window: &Window) {
-    // Don't save if window is minimized or hidden
+fn load_state_map() -> WindowStateMap {
+    let state_file = get_state_file_path();
+    match fs::read_to_string(&state_file) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => WindowStateMap::new(),
+    }
+}
+
+fn write_state_map(map: &WindowStateMap) {
+    let state_file = get_state_file_path();
+    if let Ok(json) = serde_json::to_string_pretty(map) {
+        if let Err(e) = fs::write(&state_file, json) {
+            log::warn!("Failed to save window state: {}", e);
+        }
+    }
+}
+
+/// Save the current state of a single window, writing only the fields whose
+/// flag is set in `flags`.
+pub fn save_window_state(window: &Window, flags: StateFlags) {
+    // Don't save if window is minimized or hidden (position/size would be bogus)
     if window.is_minimized().unwrap_or(false) || !window.is_visible().unwrap_or(true) {
         return;
     }
 
-    let state = match (
-        window.outer_position(),
-        window.outer_size(),
-        window.is_maximized(),
-    ) {
-        (Ok(pos), Ok(size), Ok(maximized)) => WindowState {
-            x: pos.x,
-            y: pos.y,
-            width: size.width,
-            height: size.height,
-            maximized,
-        },
-        _ => return,
-    };
+    let label = window.label().to_string();
+    let mut map = load_state_map();
+    let mut state = map.remove(&label).unwrap_or_default();
+    let maximized = window.is_maximized().unwrap_or(false);
 
-    // Don't save if maximized (we'll restore to maximized state instead)
-    if state.maximized {
-        // Just save the maximized flag
-        let state_file = get_state_file_path();
-        if let Ok(existing) = fs::read_to_string(&state_file) {
-            if let Ok(mut existing_state) = serde_json::from_str::<WindowState>(&existing) {
-                existing_state.maximized = true;
-                if let Ok(json) = serde_json::to_string_pretty(&existing_state) {
-                    let _ = fs::write(&state_file, json);
-                }
+    // While maximized, leave the saved x/y/width/height alone so the
+    // pre-maximize geometry survives on disk for the next restore.
+    if !maximized {
+        if flags.contains(StateFlags::POSITION) {
+            if let Ok(pos) = window.outer_position() {
+                state.x = pos.x;
+                state.y = pos.y;
             }
         }
-        return;
-    }
 
-    let state_file = get_state_file_path();
-    if let Ok(json) = serde_json::to_string_pretty(&state) {
-        if let Err(e) = fs::write(&state_file, json) {
-            log::warn!("Failed to save window state: {}", e);
+        if flags.contains(StateFlags::SIZE) {
+            if let Ok(size) = window.outer_size() {
+                state.width = size.width;
+                state.height = size.height;
+            }
         }
     }
+
+    if flags.contains(StateFlags::MAXIMIZED) {
+        state.maximized = maximized;
+    }
+
+    if flags.contains(StateFlags::FULLSCREEN) {
+        state.fullscreen = window.is_fullscreen().unwrap_or(false);
+    }
+
+    if flags.contains(StateFlags::VISIBLE) {
+        state.visible = window.is_visible().unwrap_or(true);
+    }
+
+    if flags.contains(StateFlags::DECORATIONS) {
+        state.decorations = window.is_decorated().unwrap_or(true);
+    }
+
+    map.insert(label, state);
+    write_state_map(&map);
 }
 
-/// Restore window state from file
-pub fn restore_window_state(window: &WebviewWindow) {
-    let state_file = get_state_file_path();
+/// Save the state of every open window, e.g. on app exit.
+pub fn save_all_window_states(app: &AppHandle, flags: StateFlags) {
+    for (_, window) in app.windows() {
+        save_window_state(&window, flags);
+    }
+}
 
-    let content = match fs::read_to_string(&state_file) {
-        Ok(c) => c,
-        Err(_) => return, // No saved state
-    };
+/// Restore a single window's state from file, applying only the fields whose
+/// flag is set in `flags`.
+pub fn restore_window_state(window: &WebviewWindow, flags: StateFlags) {
+    let label = window.label().to_string();
+    let map = load_state_map();
 
-    let state: WindowState = match serde_json::from_str(&content) {
-        Ok(s) => s,
-        Err(e) => {
-            log::warn!("Failed to parse window state: {}", e);
-            return;
-        }
+    let state = match map.get(&label) {
+        Some(s) => s,
+        None => return, // No saved state for this window
     };
 
-    // Validate state (ensure window is not off-screen)
-    if state.width < 400 || state.height < 300 {
+    // Validate state (ensure window is not absurdly small)
+    if flags.contains(StateFlags::SIZE) && (state.width < 400 || state.height < 300) {
         return; // Invalid size
     }
 
-    // Apply state
-    if state.maximized {
+    if flags.contains(StateFlags::MAXIMIZED) && state.maximized {
         let _ = window.maximize();
     } else {
-        // Set position first, then size
-        let _ = window.set_position(tauri::Position::Physical(tauri::PhysicalPosition {
-            x: state.x,
-            y: state.y,
-        }));
-        let _ = window.set_size(tauri::Size::Physical(tauri::PhysicalSize {
-            width: state.width,
-            height: state.height,
-        }));
+        if flags.contains(StateFlags::POSITION) {
+            let (x, y) = clamp_to_monitors(window, state.x, state.y, state.width, state.height);
+            let _ = window.set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }));
+        }
+
+        if flags.contains(StateFlags::SIZE) {
+            let _ = window.set_size(tauri::Size::Physical(tauri::PhysicalSize {
+                width: state.width,
+                height: state.height,
+            }));
+        }
+    }
+
+    if flags.contains(StateFlags::FULLSCREEN) {
+        let _ = window.set_fullscreen(state.fullscreen);
+    }
+
+    if flags.contains(StateFlags::VISIBLE) && !state.visible {
+        let _ = window.hide();
+    }
+
+    if flags.contains(StateFlags::DECORATIONS) {
+        let _ = window.set_decorations(state.decorations);
     }
 
     log::info!(
-        "Restored window state: {}x{} at ({}, {}), maximized: {}",
+        "Restored window state for '{}': {}x{} at ({}, {}), maximized: {}",
+        label,
         state.width,
         state.height,
         state.x,
@@ -120,3 +189,76 @@ pub fn restore_window_state(window: &WebviewWindow) {
         state.maximized
     );
 }
+
+/// Minimum strip of the window that must overlap a monitor's work area for
+/// the window to be considered reachable (roughly a title bar's height).
+const MIN_VISIBLE_STRIP: i32 = 24;
+
+/// If `(x, y, width, height)` would land off every currently-connected
+/// monitor, snap it onto the nearest monitor instead (falling back to
+/// centering on the primary monitor if no monitor info is available). This
+/// guards against the window opening invisibly after a monitor is unplugged
+/// or a resolution changes.
+fn clamp_to_monitors(window: &WebviewWindow, x: i32, y: i32, width: u32, height: u32) -> (i32, i32) {
+    let monitors = match window.available_monitors() {
+        Ok(monitors) if !monitors.is_empty() => monitors,
+        // No monitor list: check against the primary monitor alone, if known.
+        _ => match window.primary_monitor() {
+            Ok(Some(primary)) => vec![primary],
+            _ => return (x, y),
+        },
+    };
+
+    let overlaps_any = monitors.iter().any(|monitor| {
+        let pos = monitor.position();
+        let size = monitor.size();
+        let (mx0, my0) = (pos.x, pos.y);
+        let (mx1, my1) = (mx0 + size.width as i32, my0 + size.height as i32);
+
+        let ix0 = x.max(mx0);
+        let iy0 = y.max(my0);
+        let ix1 = (x + width as i32).min(mx1);
+        let iy1 = (y + MIN_VISIBLE_STRIP).min(my1);
+
+        ix1 > ix0 && iy1 > iy0
+    });
+
+    if overlaps_any {
+        return (x, y);
+    }
+
+    log::warn!(
+        "Saved window position ({}, {}) is off every connected monitor, snapping to nearest",
+        x,
+        y
+    );
+
+    let center = (x + width as i32 / 2, y + height as i32 / 2);
+    let nearest = monitors.iter().min_by_key(|monitor| {
+        let pos = monitor.position();
+        let size = monitor.size();
+        let monitor_center = (
+            pos.x + size.width as i32 / 2,
+            pos.y + size.height as i32 / 2,
+        );
+        let dx = (monitor_center.0 - center.0) as i64;
+        let dy = (monitor_center.1 - center.1) as i64;
+        dx * dx + dy * dy
+    });
+
+    let target = nearest
+        .cloned()
+        .or_else(|| window.primary_monitor().ok().flatten());
+
+    match target {
+        Some(monitor) => {
+            let pos = monitor.position();
+            let size = monitor.size();
+            // Center the window on the target monitor's work area.
+            let nx = pos.x + (size.width as i32 - width as i32).max(0) / 2;
+            let ny = pos.y + (size.height as i32 - height as i32).max(0) / 2;
+            (nx, ny)
+        }
+        None => (x, y),
+    }
+}